@@ -0,0 +1,40 @@
+//! Strength scoring against candidate generation: scoring must keep up with
+//! the generator for `min_strength_score` not to slow runs down.
+//!
+//! cargo bench --bench strength
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use imbrut::strength;
+use imbrut::utils::StringsGenerator;
+
+const CANDIDATES: usize = 200_000;
+
+fn per_candidate(elapsed: Duration) -> f64 {
+    elapsed.as_nanos() as f64 / CANDIDATES as f64
+}
+
+fn main() {
+    let allowed_chars = vec!["abcdefghijklmnopqrstuvwxyz0123456789!@#".to_string()];
+
+    let start = Instant::now();
+    let candidates: Vec<String> = StringsGenerator::new(&allowed_chars, 8..=8).take(CANDIDATES).collect();
+    let generation = start.elapsed();
+
+    let wordlist = ["password1", "Summer2023!", "qwerty123", "x7#kQ!9vLp2@", "letmein", "dragon99"];
+    let start = Instant::now();
+    for candidate in &candidates {
+        black_box(strength::score(candidate));
+    }
+    let generated = start.elapsed();
+    let start = Instant::now();
+    for i in 0..CANDIDATES {
+        black_box(strength::score(wordlist[i % wordlist.len()]));
+    }
+    let words = start.elapsed();
+
+    println!("generation:           {:>8.0} ns/candidate", per_candidate(generation));
+    println!("scoring (generated):  {:>8.0} ns/candidate", per_candidate(generated));
+    println!("scoring (wordlist):   {:>8.0} ns/candidate", per_candidate(words));
+}
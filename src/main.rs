@@ -1,6 +1,225 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use imbrut::application::Application;
+use imbrut::cli::{self, Args};
+use imbrut::error::ImbrutError;
+use imbrut::manifest::{normalize_target, Manifest};
+use imbrut::settings::{layered_config, migrate_config_file, process_env, show_config, write_template, DEFAULT_MANIFEST};
+use imbrut::stats::WordlistStats;
+use imbrut::strategy::deconfliction;
+use imbrut::utils::FileWithStrings;
+
+const USAGE: &str = "usage: imbrut [options]
+       imbrut migrate-config <in.yml> [-o <out.yml>]
+       imbrut [options] export-candidates [--skip N] [--limit M] [--pairs] [-o <out.txt>]
+       imbrut [options] smtp-enum [-o <usernames.txt>]
+       imbrut [options] plan [--json] [-o <plan.csv>]
+       imbrut wordlist-stats [--strength-histogram] <wordlist.txt>
+       imbrut [options] config show [--origins]
+       imbrut [options] config suggest <url>
+       imbrut [options] config init [-o <config.yml>]
+       imbrut [options] config check";
+
+/// Exit status of a run stopped on one of its `limits`: not an error, but
+/// not over either, the next run resumes it.
+const EXIT_LIMIT: i32 = 3;
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn migrate_config(args: &[String]) -> Result<(), ImbrutError> {
+    let (input, output) = match args {
+        [input] => (input, None),
+        [input, flag, output] if flag == "-o" => (input, Some(output.as_str())),
+        _ => usage(),
+    };
+    for description in migrate_config_file(input, output)? {
+        eprintln!("applied migration: {}", description);
+    }
+    Ok(())
+}
+
+fn export_candidates(global: &Args, args: &[String]) -> Result<(), ImbrutError> {
+    let (mut skip, mut limit, mut pairs, mut output) = (0, None, false, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--skip" => skip = args.next().and_then(|x| x.parse().ok()).unwrap_or_else(|| usage()),
+            "--limit" => limit = Some(args.next().and_then(|x| x.parse().ok()).unwrap_or_else(|| usage())),
+            "--pairs" => pairs = true,
+            "-o" => output = Some(args.next().unwrap_or_else(|| usage()).as_str()),
+            _ => usage(),
+        }
+    }
+    let count = Application::new(global)?.export_candidates(skip, limit, pairs, output)?;
+    eprintln!("exported {} candidates", count);
+    Ok(())
+}
+
+fn smtp_enum(global: &Args, args: &[String]) -> Result<(), ImbrutError> {
+    let output = match args {
+        [] => None,
+        [flag, output] if flag == "-o" => Some(output.as_str()),
+        _ => usage(),
+    };
+    let report = Application::new(global)?.enumerate_usernames(output)?;
+    match report.method {
+        Some(method) => eprintln!(
+            "{} valid usernames via {} ({} invalid, {} unverifiable)",
+            report.valid.len(), method, report.invalid, report.unverifiable
+        ),
+        None => eprintln!("the server accepts any username with both VRFY and RCPT TO, it cannot be enumerated"),
+    }
+    Ok(())
+}
+
+fn plan(global: &Args, args: &[String]) -> Result<(), ImbrutError> {
+    let (mut json, mut output) = (false, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-o" => output = Some(args.next().unwrap_or_else(|| usage()).as_str()),
+            _ => usage(),
+        }
+    }
+    let windows = Application::new(global)?.plan()?;
+    let rendered = match json {
+        true => format!("{:#}\n", deconfliction::to_json(&windows)),
+        false => deconfliction::to_csv(&windows),
+    };
+    match output {
+        Some(path) => fs::write(path, rendered).map_err(|source| ImbrutError::Io { path: path.to_string(), source })?,
+        None => print!("{}", rendered),
+    }
+    eprintln!(
+        "{} attempts planned in {} account windows",
+        windows.iter().map(|x| x.attempts).sum::<u64>(), windows.len()
+    );
+    Ok(())
+}
+
+fn wordlist_stats(args: &[String]) -> Result<(), ImbrutError> {
+    let (histogram, path) = match args {
+        [path] => (false, path),
+        [flag, path] if flag == "--strength-histogram" => (true, path),
+        _ => usage(),
+    };
+    #[cfg(feature = "strength")]
+    let scorer: Option<fn(&str) -> u8> = histogram.then_some(imbrut::strength::score);
+    #[cfg(not(feature = "strength"))]
+    let scorer: Option<fn(&str) -> u8> = match histogram {
+        true => return Err(ImbrutError::Ui("--strength-histogram needs the `strength` feature".to_string())),
+        false => None,
+    };
+    let stats = WordlistStats::collect(FileWithStrings::new(path)?, scorer);
+    stats.write_to(&mut io::stdout().lock()).map_err(|source| ImbrutError::Io { path: "-".to_string(), source })
+}
+
+fn ago(elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        x if x >= 86400 => format!("{} days", x / 86400),
+        x if x >= 3600 => format!("{} hours", x / 3600),
+        x => format!("{} minutes", x / 60),
+    }
+}
+
+/// Print the template an earlier run saved for `url`, with its age.
+fn suggest(config_file: &str, url: &str) -> Result<(), ImbrutError> {
+    let manifest = match Path::new(config_file).exists() {
+        true => layered_config(config_file, &process_env())?.tree["manifest"].as_str().map(str::to_string),
+        false => None,
+    };
+    let manifest = Manifest::load(manifest.as_deref().unwrap_or(DEFAULT_MANIFEST))?;
+    let key = normalize_target(url).ok_or_else(|| ImbrutError::Ui(format!("`{}` is not a URL", url)))?;
+    let Some(template) = manifest.template(url)? else {
+        eprintln!("no template saved for {}", key);
+        return Ok(());
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+    eprintln!(
+        "{} was seen {} ago, template saved by imbrut {}",
+        key, ago(Duration::from_secs(now.saturating_sub(template.saved_unix))), template.imbrut_version
+    );
+    for description in &template.migrations {
+        eprintln!("applied migration: {}", description);
+    }
+    let yaml = serde_yaml::to_string(&template.config)
+        .map_err(|e| ImbrutError::Ui(format!("cannot render the template: {}", e)))?;
+    print!("{}", yaml);
+    Ok(())
+}
+
+fn init_config(path: &str) -> Result<(), ImbrutError> {
+    write_template(path)?;
+    eprintln!("wrote a config template to {}", path);
+    Ok(())
+}
+
+fn config(global: &Args, args: &[String]) -> Result<(), ImbrutError> {
+    let config_file = global.config_file(&process_env());
+    let origins = match args {
+        [show] if show == "show" => false,
+        [show, flag] if show == "show" && flag == "--origins" => true,
+        [command, url] if command == "suggest" => return suggest(&config_file, url),
+        [init] if init == "init" => return init_config(&config_file),
+        [init, flag, output] if init == "init" && flag == "-o" => return init_config(output),
+        [check] if check == "check" => return Application::new(global)?.check(&mut io::stdout().lock()),
+        _ => usage(),
+    };
+    let layered = global.layered(&process_env())?;
+    print!("{}", show_config(&layered, origins)?);
+    Ok(())
+}
+
+fn run(args: Args, rest: &[String]) -> Result<(), ImbrutError> {
+    if let Some(arg) = rest.first() {
+        return Err(ImbrutError::Ui(format!("unknown argument `{}`, see --help", arg)));
+    }
+    let config_file = args.config_file(&process_env());
+    if !Path::new(&config_file).exists() {
+        write_template(&config_file)?;
+        return Err(ImbrutError::Ui(format!(
+            "no config found, wrote a template to {}: fill in the target and wordlists, then run again", config_file
+        )));
+    }
+    let app = Application::new(&args)?;
+    app.run()?;
+    if app.limit_reached() {
+        process::exit(EXIT_LIMIT);
+    }
+    Ok(())
+}
 
 fn main() {
-    let app = Application::new();
-    app.run();
+    let args: Vec<String> = env::args().skip(1).collect();
+    // The options go before or after a command, which reads the config
+    // with them as a run would.
+    let result = Args::extract(&args).and_then(|(global, rest)| {
+        if global.help {
+            print!("{}\n\n{}", USAGE, cli::help());
+            return Ok(());
+        }
+        match rest.first().map(String::as_str) {
+            Some("migrate-config") => migrate_config(&rest[1..]),
+            Some("export-candidates") => export_candidates(&global, &rest[1..]),
+            Some("smtp-enum") => smtp_enum(&global, &rest[1..]),
+            Some("plan") => plan(&global, &rest[1..]),
+            Some("wordlist-stats") => wordlist_stats(&rest[1..]),
+            Some("config") => config(&global, &rest[1..]),
+            _ => run(global, &rest),
+        }
+    });
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,27 @@
+mod testserver;
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use testserver::{app, TempDir};
+
+#[test]
+fn test_salted_sha256() {
+    let dir = TempDir::new("hash-sha256");
+    let passwords = dir.file("passwords.txt", "letmein\nqwerty\npassword\nhunter2\n");
+    let started = Instant::now();
+    // sha256("saltpassword"), behind a strategy pausing a minute after
+    // every candidate: offline, the pauses are skipped.
+    app(&format!(
+        "proto: hash\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: [{{requests: 1}}, {{sleep: 60000}}]\nrecent_attempts: 0\n\
+        report: {{json: '{}'}}\ntarget: {{algorithm: sha256, hash: 13601bda4ea78e55a07b98866d2be6be0744e3866f13c00c811cab608a28f322, \
+        salt: salt, format: '{{{{salt}}}}{{{{password}}}}'}}",
+        passwords, dir.path("promoted.txt"), dir.path("manifest.json"), dir.path("report.json"),
+    )).run().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(30), "{:?}", started.elapsed());
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "password\n");
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path("report.json")).unwrap()).unwrap();
+    assert_eq!((report["hits"][0]["attempt"].as_u64(), report["run"]["rate_unit"].as_str()), (Some(2), Some("candidates")));
+    assert_eq!(report["target"]["keys"]["algorithm"], "sha256");
+}
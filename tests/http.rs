@@ -0,0 +1,482 @@
+mod testserver;
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use imbrut::error::ImbrutError;
+
+use testserver::{app, tls_fixture, HttpServer, Response, TempDir};
+
+/// A login form accepting `admin:s3cret`.
+fn login_form() -> HttpServer {
+    HttpServer::start(|request| match request.body.as_str() {
+        "username=admin&password=s3cret" => Response::new(200, "<h1>Welcome admin</h1>"),
+        _ => Response::new(200, "<p>Login failed</p>"),
+    })
+}
+
+/// A run of every password in `passwords` for `admin` against `target`.
+fn config(dir: &TempDir, passwords: &str, target: &str) -> String {
+    format!(
+        "proto: http\ndict_type: file\npasswords_file: '{}'\nusername: admin\n\
+        promoted_file: '{}'\nmanifest: '{}'\nrecent_attempts: 0\n\
+        strategy: []\ntarget:\n{}",
+        dir.file("passwords.txt", passwords), dir.path("promoted.txt"), dir.path("manifest.json"), target,
+    )
+}
+
+#[test]
+fn test_form_login() {
+    let server = login_form();
+    let dir = TempDir::new("http-form");
+    app(&config(&dir, "123456\ns3cret\nletmein\n", &format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+", server.uri("/login")))).run().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    let requests = server.requests();
+    assert!(requests.iter().all(|x| x.method == "POST" && x.path == "/login"));
+    assert_eq!(requests.iter().filter(|x| x.body == "username=admin&password=s3cret").count(), 1);
+    // The run ends at the first hit.
+    assert!(!requests.iter().any(|x| x.body == "username=admin&password=letmein"));
+}
+
+#[test]
+fn test_hits_are_written_as_made() {
+    let server = login_form();
+    let dir = TempDir::new("http-output");
+    let output = dir.path("hits.csv");
+    app(&format!("output: {{path: '{}', format: csv}}\n{}", output, config(&dir, "123456\ns3cret\n", &format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+", server.uri("/login"))))).run().unwrap();
+
+    let csv = fs::read_to_string(&output).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines[0], "timestamp,target,protocol,username,password,attempt,status,latency_ms");
+    let fields: Vec<_> = lines[1].split(',').collect();
+    assert_eq!(fields[1..7], [server.uri("/login").as_str(), "http", "admin", "s3cret", "1", "200"]);
+    // Then the totals of the run, as comments.
+    assert_eq!(lines.iter().filter(|x| !x.starts_with('#')).count(), 2);
+    assert!(lines[2].starts_with("# ") && lines[2].contains(" summary: 2 requests in "), "{}", csv);
+}
+
+#[test]
+fn test_basic_auth() {
+    let server = HttpServer::start(|request| match request.header("authorization") {
+        // admin:s3cret
+        Some("Basic YWRtaW46czNjcmV0") => Response::new(200, "<h1>Router status</h1>"),
+        _ => Response::new(401, "").header("www-authenticate", "Basic realm=\"router\""),
+    });
+    let dir = TempDir::new("http-basic");
+    app(&config(&dir, "admin\ns3cret\n", &format!("
+    auth_type: basic
+    uri: '{}'
+    method: GET
+    headers: {{}}
+    success_codes: [200]
+    fail_codes: [401]
+    success_if_contains: [Router status]
+    fail_if_contains: []
+", server.uri("/")))).run().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    assert!(server.requests().iter().all(|x| x.method == "GET" && x.body.is_empty()));
+}
+
+#[test]
+fn test_form_field_names() {
+    let server = HttpServer::start(|_| Response::new(200, "<p>Login failed</p>"));
+    let dir = TempDir::new("http-fields");
+    app(&config(&dir, "p&ss\n", &format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+    username_field: log
+    password_field: pwd
+    form_fields: {{wp-submit: Log In, testcookie: 1}}
+", server.uri("/wp-login.php")))).run().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "");
+    let requests = server.requests();
+    assert_eq!(requests.last().unwrap().body, "log=admin&pwd=p%26ss&testcookie=1&wp-submit=Log+In");
+    assert_eq!(requests.last().unwrap().header("content-type"), Some("application/x-www-form-urlencoded"));
+}
+
+#[test]
+fn test_json_login() {
+    let server = HttpServer::start(|request| {
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap_or_default();
+        match (body["auth"]["user"].as_str(), body["auth"]["secret"].as_str()) {
+            (Some("admin"), Some("s3\"cret")) => Response::new(200, "{\"token\": \"abc\"}"),
+            _ => Response::new(401, "{\"error\": \"invalid credentials\"}"),
+        }
+    });
+    let dir = TempDir::new("http-json");
+    app(&config(&dir, "123456\ns3\"cret\n", &format!(r#"
+    auth_type: json
+    body_template: '{{"auth": {{"user": "{{{{username}}}}", "secret": "{{{{password}}}}"}}}}'
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    fail_codes: [401]
+    success_if_contains: [token]
+    fail_if_contains: []
+"#, server.uri("/api/login")))).run().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3\"cret\n");
+    // The pre-flight request carries no credentials.
+    let attempts: Vec<_> = server.requests().into_iter().filter(|x| !x.body.is_empty()).collect();
+    assert_eq!(attempts.len(), 2);
+    assert!(attempts.iter().all(|x| x.header("content-type") == Some("application/json")));
+}
+
+#[test]
+fn test_raw_templates() {
+    let server = HttpServer::start(|request| {
+        let valid = request.header("x-auth-user") == Some("admin")
+            && request.path == "/soap/admin?p=s3%20cr%2Ft"
+            && request.body == "<login><secret>s3 cr/t</secret></login>";
+        match valid {
+            true => Response::new(200, "<ok/>"),
+            false => Response::new(401, "<fault/>"),
+        }
+    });
+    let dir = TempDir::new("http-raw");
+    app(&config(&dir, "123456\ns3 cr/t\n", &format!("
+    auth_type: raw
+    uri: '{}/soap/{{{{username}}}}?p={{{{password}}}}'
+    method: POST
+    headers: {{X-Auth-User: '{{{{username}}}}', Content-Type: text/xml}}
+    body: '<login><secret>{{{{password}}}}</secret></login>'
+    success_codes: [200]
+    fail_codes: [401]
+    success_if_contains: [ok]
+    fail_if_contains: []
+", server.uri("")))).run().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3 cr/t\n");
+    let attempts: Vec<_> = server.requests().into_iter().filter(|x| !x.body.is_empty()).collect();
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(attempts[0].path, "/soap/admin?p=123456");
+    assert!(attempts.iter().all(|x| x.header("content-type") == Some("text/xml")));
+}
+
+#[test]
+fn test_token_placements() {
+    let server = HttpServer::start(|request| {
+        let valid = request.header("authorization") == Some("Bearer k3y")
+            || request.header("x-api-key") == Some("k3y")
+            || request.path.ends_with("?api_key=k3y");
+        match valid {
+            true => Response::new(200, "{\"data\": []}"),
+            false => Response::new(401, "{\"error\": \"unauthorized\"}"),
+        }
+    });
+    for (name, placement) in [("bearer", ""), ("header", "token_header: X-API-Key"), ("query", "token_param: api_key")] {
+        let dir = TempDir::new(&format!("http-token-{}", name));
+        app(&config(&dir, "guess\nk3y\nlater\n", &format!("
+    auth_type: token
+    {}
+    uri: '{}'
+    method: GET
+    headers: {{}}
+    success_codes: [200]
+    fail_codes: [401]
+    success_if_contains: [data]
+    fail_if_contains: []
+", placement, server.uri("/api/v1/me")))).run().unwrap();
+        assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "k3y\n", "{}", name);
+    }
+    // Each candidate is sent once, alone: a pre-flight and two attempts per run.
+    assert_eq!(server.requests().len(), 9);
+}
+
+/// A login page handing out a session cookie and a token bound to it; the
+/// form accepts `admin:s3cret` only with both.
+fn csrf_login() -> HttpServer {
+    let sessions = Mutex::new(HashMap::new());
+    let opened = AtomicUsize::new(0);
+    HttpServer::start(move |request| {
+        if request.method == "GET" {
+            let id = opened.fetch_add(1, Ordering::Relaxed);
+            let token = format!("tok{:04}", id * 7919 % 10000);
+            sessions.lock().unwrap().insert(format!("sid{}", id), token.clone());
+            let page = format!("<form><input type=\"hidden\" name=\"_token\" value=\"{}\"></form>", token);
+            return Response::new(200, &page).header("set-cookie", &format!("sid=sid{}; Path=/; HttpOnly", id));
+        }
+        let cookie = request.header("cookie").and_then(|x| x.strip_prefix("sid=")).unwrap_or_default();
+        let expected = sessions.lock().unwrap().get(cookie).map(|token| format!("username=admin&password=s3cret&_token={}", token));
+        match expected {
+            Some(expected) if request.body == expected => Response::new(200, "<h1>Welcome admin</h1>"),
+            Some(_) => Response::new(200, "<p>Login failed</p>"),
+            None => Response::new(419, "session expired"),
+        }
+    })
+}
+
+fn csrf_target(server: &HttpServer, csrf: &str) -> String {
+    format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    fail_codes: [419]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+    pre_request: {{uri: /login}}
+{}", server.uri("/login"), csrf)
+}
+
+#[test]
+fn test_csrf_login() {
+    let server = csrf_login();
+    let dir = TempDir::new("http-csrf");
+    app(&config(&dir, "123456\ns3cret\n", &csrf_target(&server, "    csrf: {input: _token}\n"))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    // The pre-flight request carries no credentials, and no session.
+    let attempts: Vec<_> = server.requests().into_iter().filter(|x| !x.body.is_empty()).collect();
+    assert_eq!(attempts.len(), 2);
+    assert!(attempts.iter().all(|x| x.header("cookie").is_some_and(|x| x.starts_with("sid=sid"))));
+
+    // Without the token, the right password is refused.
+    let dir = TempDir::new("http-csrf-missing");
+    app(&config(&dir, "s3cret\n", &csrf_target(&server, ""))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "");
+
+    let err = app(&config(&dir, "s3cret\n", &csrf_target(&server, "    csrf: {input: authenticity_token}\n"))).run().unwrap_err();
+    assert!(err.to_string().contains("`target.csrf`: no token found in the answer of `pre_request` (200 OK)"), "{}", err);
+}
+
+/// A form login answering 302 either way, to the dashboard with a session
+/// cookie for `admin:s3cret`, back to the form otherwise.
+fn redirecting_login() -> HttpServer {
+    HttpServer::start(|request| match (request.method.as_str(), request.path.as_str(), request.body.as_str()) {
+        ("POST", _, "username=admin&password=s3cret") => Response::new(302, "")
+            .header("location", "/dashboard")
+            .header("set-cookie", "session=0badf00d; HttpOnly"),
+        ("POST", _, _) => Response::new(302, "").header("location", "/login?error=1"),
+        (_, "/dashboard", _) => Response::new(200, "<h1>Welcome admin</h1>"),
+        _ => Response::new(200, "<p>Login failed</p>"),
+    })
+}
+
+fn redirect_target(server: &HttpServer, rules: &str) -> String {
+    format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+{}", server.uri("/login"), rules)
+}
+
+#[test]
+fn test_redirect_rules() {
+    let server = redirecting_login();
+    let dir = TempDir::new("http-redirect");
+    app(&config(&dir, "123456\ns3cret\n", &redirect_target(&server, "
+    follow_redirects: false
+    success_if_redirects_to: [/dashboard]
+    fail_if_redirects_to: [error=1]
+"))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    assert!(!server.requests().iter().any(|x| x.method == "GET"));
+
+    let dir = TempDir::new("http-redirect-cookie");
+    app(&config(&dir, "123456\ns3cret\n", &redirect_target(&server, "
+    follow_redirects: false
+    success_if_header: ['Set-Cookie: session=']
+"))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+
+    // Followed, the pages redirected to are matched as before.
+    let dir = TempDir::new("http-redirect-followed");
+    app(&config(&dir, "123456\ns3cret\n", &redirect_target(&server, ""))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    assert!(server.requests().iter().any(|x| x.method == "GET" && x.path == "/dashboard"));
+
+    let err = app(&config(&dir, "s3cret\n", &redirect_target(&server, "    success_if_redirects_to: [/dashboard]\n"))).run().unwrap_err();
+    assert!(err.to_string().contains("`target.success_if_redirects_to`: needs `follow_redirects: false`"), "{}", err);
+}
+
+/// A form login against a TLS server, with the `tls` table of the target.
+fn tls_config(dir: &TempDir, server: &HttpServer, tls: &str) -> String {
+    config(dir, "123456\ns3cret\n", &format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+    {}
+", server.uri("/login"), tls))
+}
+
+fn tls_login_form(cert: &str, client_auth: bool) -> HttpServer {
+    HttpServer::start_tls(cert, client_auth, |request| match request.body.as_str() {
+        "username=admin&password=s3cret" => Response::new(200, "<h1>Welcome admin</h1>"),
+        _ => Response::new(200, "<p>Login failed</p>"),
+    })
+}
+
+#[test]
+fn test_tls_untrusted_certificate() {
+    let server = tls_login_form("server", false);
+    let dir = TempDir::new("http-tls-untrusted");
+    let err = app(&tls_config(&dir, &server, "")).run().err().unwrap();
+    assert!(err.to_string().contains("certificate verify failed"), "{}", err);
+    assert!(server.requests().is_empty());
+
+    app(&tls_config(&dir, &server, "tls: {insecure: true}")).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+}
+
+#[test]
+fn test_tls_ca_file() {
+    let server = tls_login_form("server", false);
+    let dir = TempDir::new("http-tls-ca");
+    app(&tls_config(&dir, &server, &format!("tls: {{ca_file: '{}'}}", tls_fixture("ca.pem")))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+}
+
+#[test]
+fn test_tls_client_certificate() {
+    let server = tls_login_form("server", true);
+    let dir = TempDir::new("http-tls-client");
+    // Refused during the handshake without a client certificate.
+    assert!(app(&tls_config(&dir, &server, &format!("tls: {{ca_file: '{}'}}", tls_fixture("ca.pem")))).run().is_err());
+    assert!(server.requests().is_empty());
+
+    app(&tls_config(&dir, &server, &format!(
+        "tls: {{ca_file: '{}', client_cert: '{}', client_key: '{}'}}",
+        tls_fixture("ca.pem"), tls_fixture("client.pem"), tls_fixture("client.key"),
+    ))).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+}
+
+/// The error kind a run against `server` ends with, and its message.
+fn failed_run(server: &HttpServer, tls: &str) -> (&'static str, String) {
+    let dir = TempDir::new("http-tls-errors");
+    match app(&tls_config(&dir, server, tls)).run().err().unwrap() {
+        ImbrutError::Proto { source, .. } => (source.kind(), source.to_string()),
+        err => panic!("not a proto error: {}", err),
+    }
+}
+
+#[test]
+fn test_tls_error_kinds() {
+    let trusted = format!("tls: {{ca_file: '{}'}}", tls_fixture("ca.pem"));
+    let (kind, message) = failed_run(&tls_login_form("server", false), "");
+    assert_eq!(kind, "tls.untrusted", "{}", message);
+    assert!(message.contains("server certificate: subject CN=localhost, issuer CN=imbrut test CA"), "{}", message);
+
+    let (kind, message) = failed_run(&tls_login_form("expired", false), &trusted);
+    assert_eq!(kind, "tls.expired", "{}", message);
+    assert!(message.contains("to Jan  1 00:00:00 2021 GMT"), "{}", message);
+
+    let (kind, message) = failed_run(&tls_login_form("wronghost", false), &trusted);
+    assert_eq!(kind, "tls.hostname", "{}", message);
+    assert!(message.contains("subject CN=wrong.example"), "{}", message);
+
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let dir = TempDir::new("http-closed");
+    let err = app(&config(&dir, "s3cret\n", &format!("
+    auth_type: form
+    uri: 'http://{}/login'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: []
+", closed))).run().err().unwrap();
+    assert!(matches!(err, ImbrutError::Proto { ref source, .. } if source.kind() == "network.refused"), "{}", err);
+}
+
+#[test]
+fn test_retry_after() {
+    let served = AtomicUsize::new(0);
+    let server = HttpServer::start(move |request| {
+        if served.fetch_add(1, Ordering::Relaxed) % 3 == 2 {
+            return Response::new(429, "slow down").header("retry-after", "2");
+        }
+        match request.body.as_str() {
+            "username=admin&password=s3cret" => Response::new(200, "<h1>Welcome admin</h1>"),
+            _ => Response::new(200, "<p>Login failed</p>"),
+        }
+    });
+    let dir = TempDir::new("http-retry-after");
+    // Two seconds asked, one honored.
+    let config = config(&dir, "123456\nletmein\nqwerty\ns3cret\n", &format!("
+    auth_type: form
+    uri: '{}'
+    method: POST
+    headers: {{}}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+", server.uri("/login")));
+    let started = std::time::Instant::now();
+    app(&format!("max_retry_after: 1\n{}", config)).run().unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    // Throttled candidates are sent again rather than counted as rejected.
+    let bodies: Vec<_> = server.requests().into_iter().map(|x| x.body).filter(|x| !x.is_empty()).collect();
+    assert_eq!(bodies.iter().filter(|x| x.ends_with("=letmein")).count(), 2);
+    assert_eq!(bodies.iter().filter(|x| x.ends_with("=s3cret")).count(), 2);
+    assert!(elapsed >= std::time::Duration::from_secs(2) && elapsed < std::time::Duration::from_secs(4), "{:?}", elapsed);
+}
+
+#[test]
+fn test_unreachable_target() {
+    let login = "
+    auth_type: form
+    method: POST
+    headers: {}
+    success_codes: [200]
+    success_if_contains: [Welcome]
+    fail_if_contains: [Login failed]
+";
+    let backoff = "unreachable_threshold: 2\nunreachable_backoff: 50ms\nunreachable_give_up_after: 2\n";
+    // Nothing listens there: the run backs off, re-probes and gives up
+    // instead of failing on the first refused connection.
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let dir = TempDir::new("http-unreachable");
+    let target = config(&dir, "123456\nletmein\nqwerty\ns3cret\n", &format!("    uri: 'http://{}/login'{}", closed, login));
+    app(&format!("{}report: {{json: '{}'}}\n{}", backoff, dir.path("report.json"), target)).run().unwrap();
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path("report.json")).unwrap()).unwrap();
+    let kinds: Vec<_> = report["events"].as_array().unwrap().iter().map(|x| x["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["unreachable", "unreachable", "gave up", "stopped"]);
+    assert!(fs::read_to_string(dir.path("promoted.txt")).unwrap_or_default().is_empty());
+
+    // The same settings leave a live target alone.
+    let server = login_form();
+    let dir = TempDir::new("http-reachable");
+    let target = config(&dir, "123456\nletmein\nqwerty\ns3cret\n", &format!("    uri: '{}'{}", server.uri("/login"), login));
+    app(&format!("{}{}", backoff, target)).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+}
@@ -0,0 +1,20 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, tls_fixture, Replayer, TempDir};
+
+#[test]
+fn test_login_reconnects() {
+    let replayer = Replayer::fixture("imap-login.transcript");
+    let dir = TempDir::new("imap-login");
+    app(&format!(
+        "proto: imap\nusername: admin\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\n\
+        target: {{host: 127.0.0.1, port: {}, tls: {{ca_file: '{}'}}}}",
+        dir.file("passwords.txt", "123456\nletmein\npässword\ns3cret\n"), dir.path("promoted.txt"), dir.path("manifest.json"),
+        replayer.port, tls_fixture("ca.pem"),
+    )).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    replayer.assert_followed(3);
+}
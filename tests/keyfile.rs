@@ -0,0 +1,37 @@
+mod testserver;
+
+use std::fs;
+use std::path::Path;
+
+use testserver::{app, TempDir};
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/keys").join(name).display().to_string()
+}
+
+fn crack(dir: &TempDir, key: &str) -> serde_json::Value {
+    let passwords = dir.file("passwords.txt", "letmein\nqwerty\nhunter2\ncorrect horse\n");
+    app(&format!(
+        "proto: keyfile\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\n\
+        report: {{json: '{}'}}\ntarget: {{key_file: '{}'}}",
+        passwords, dir.path("promoted.txt"), dir.path("manifest.json"), dir.path("report.json"), fixture(key),
+    )).run().unwrap();
+    serde_json::from_str(&fs::read_to_string(dir.path("report.json")).unwrap()).unwrap()
+}
+
+#[test]
+fn test_openssh_passphrase() {
+    let dir = TempDir::new("keyfile-openssh");
+    let report = crack(&dir, "rsa-cbc");
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "correct horse\n");
+    assert_eq!(report["run"]["rate_unit"], "candidates");
+    assert_eq!(report["target"]["keys"]["key_file"], fixture("rsa-cbc"));
+}
+
+#[test]
+fn test_pem_passphrase() {
+    let dir = TempDir::new("keyfile-pem");
+    crack(&dir, "rsa-3des.pem");
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "hunter2\n");
+}
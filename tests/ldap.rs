@@ -0,0 +1,54 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, tls_fixture, Bind, LdapServer, TempDir};
+
+const PEOPLE: &str = "ou=people,dc=example,dc=com";
+
+/// Accepts `s3cret` for alice, refuses anything else.
+fn server(ldaps: bool) -> LdapServer {
+    LdapServer::start(ldaps, |dn, password| match (dn, password) {
+        (dn, "s3cret") if dn == format!("uid=alice,{}", PEOPLE) => (0, String::new()),
+        _ => (49, "invalid credentials".to_string()),
+    })
+}
+
+/// The accounts of the hits of the run.
+fn run(dir: &TempDir, usernames: &str, target: &str) -> Vec<String> {
+    app(&format!(
+        "proto: ldap\nusernames_file: '{}'\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\nmax_retry_after: 0\n\
+        report: {{json: '{}'}}\ntarget: {{tls: {{ca_file: '{}'}}, {}}}",
+        dir.file("usernames.txt", usernames), dir.file("passwords.txt", "letmein\ns3cret\n"),
+        dir.path("promoted.txt"), dir.path("manifest.json"), dir.path("report.json"), tls_fixture("ca.pem"), target,
+    )).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path("report.json")).unwrap()).unwrap();
+    report["hits"].as_array().unwrap().iter().map(|x| x["account"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_dn_template_start_tls() {
+    let server = server(false);
+    let dir = TempDir::new("ldap-starttls");
+    let hits = run(
+        &dir, "bob\nalice\n",
+        &format!("uri: 'ldap://127.0.0.1:{}', start_tls: true, dn_template: 'uid={{{{username}}}},{}'", server.port, PEOPLE),
+    );
+    // Reported under the DN bound, not the bare uid.
+    assert_eq!(hits, [format!("uid=alice,{}", PEOPLE)]);
+    let binds = server.binds();
+    assert!(binds.iter().all(|x| x.tls), "{:?}", binds);
+    assert!(binds.contains(&Bind { dn: format!("uid=alice,{}", PEOPLE), password: "s3cret".to_string(), tls: true }));
+    assert!(binds.iter().any(|x| x.dn == format!("uid=bob,{}", PEOPLE)));
+}
+
+#[test]
+fn test_raw_dn_ldaps() {
+    let server = server(true);
+    let dir = TempDir::new("ldap-ldaps");
+    let hits = run(&dir, &format!("uid=alice,{}\n", PEOPLE), &format!("uri: 'ldaps://127.0.0.1:{}'", server.port));
+    assert_eq!(hits, [format!("uid=alice,{}", PEOPLE)]);
+    assert_eq!(server.binds().len(), 2);
+}
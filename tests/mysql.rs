@@ -0,0 +1,37 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, Login, MysqlServer, TempDir};
+
+/// The accounts of the hits of the run.
+fn run(dir: &TempDir, server: &MysqlServer, target: &str) -> Vec<String> {
+    app(&format!(
+        "proto: mysql\nusernames_file: '{}'\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\nmax_retry_after: 0\n\
+        report: {{json: '{}'}}\ntarget: {{host: 127.0.0.1, port: {}{}}}",
+        dir.file("usernames.txt", "admin\nroot\n"), dir.file("passwords.txt", "letmein\ns3cret\n"),
+        dir.path("promoted.txt"), dir.path("manifest.json"), dir.path("report.json"), server.port, target,
+    )).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path("report.json")).unwrap()).unwrap();
+    report["hits"].as_array().unwrap().iter().map(|x| x["account"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_native_password() {
+    let server = MysqlServer::start(&[("root", "s3cret")]);
+    let dir = TempDir::new("mysql-native");
+    assert_eq!(run(&dir, &server, ""), ["root"]);
+    let logins = server.logins();
+    assert!(logins.contains(&Login { user: "root".to_string(), database: String::new(), accepted: true }), "{:?}", logins);
+    assert!(logins.iter().any(|x| x.user == "admin" && !x.accepted), "{:?}", logins);
+}
+
+#[test]
+fn test_database() {
+    let server = MysqlServer::start(&[("root", "s3cret")]);
+    let dir = TempDir::new("mysql-database");
+    assert_eq!(run(&dir, &server, ", database: app"), ["root"]);
+    assert!(server.logins().iter().all(|x| x.database == "app"));
+}
@@ -0,0 +1,20 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, Replayer, TempDir};
+
+#[test]
+fn test_login_reconnects() {
+    let replayer = Replayer::fixture("pop3-login.transcript");
+    let dir = TempDir::new("pop3-login");
+    app(&format!(
+        "proto: pop3\nusername: admin\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\nmax_retry_after: 0\n\
+        target: {{host: 127.0.0.1, port: {}}}",
+        dir.file("passwords.txt", "123456\nletmein\nqwerty\ns3cret\n"), dir.path("promoted.txt"), dir.path("manifest.json"),
+        replayer.port,
+    )).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    replayer.assert_followed(2);
+}
@@ -0,0 +1,38 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, Login, PostgresServer, TempDir};
+
+/// The accounts of the hits of the run.
+fn run(dir: &TempDir, server: &PostgresServer, target: &str) -> Vec<String> {
+    app(&format!(
+        "proto: postgres\nusernames_file: '{}'\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\nmax_retry_after: 0\n\
+        report: {{json: '{}'}}\ntarget: {{host: 127.0.0.1, port: {}{}}}",
+        dir.file("usernames.txt", "admin\npostgres\n"), dir.file("passwords.txt", "letmein\ns3cret\n"),
+        dir.path("promoted.txt"), dir.path("manifest.json"), dir.path("report.json"), server.port, target,
+    )).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path("report.json")).unwrap()).unwrap();
+    report["hits"].as_array().unwrap().iter().map(|x| x["account"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_md5_password() {
+    let server = PostgresServer::start(&[("postgres", "s3cret")]);
+    let dir = TempDir::new("postgres-md5");
+    assert_eq!(run(&dir, &server, ""), ["postgres"]);
+    let logins = server.logins();
+    // The database defaults to the user's own.
+    assert!(logins.contains(&Login { user: "postgres".to_string(), database: "postgres".to_string(), accepted: true }), "{:?}", logins);
+    assert!(logins.contains(&Login { user: "admin".to_string(), database: "admin".to_string(), accepted: false }), "{:?}", logins);
+}
+
+#[test]
+fn test_database() {
+    let server = PostgresServer::start(&[("postgres", "s3cret")]);
+    let dir = TempDir::new("postgres-database");
+    assert_eq!(run(&dir, &server, ", database: app"), ["postgres"]);
+    assert!(server.logins().iter().all(|x| x.database == "app"));
+}
@@ -0,0 +1,32 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, Replayer, TempDir};
+
+fn config(dir: &TempDir, port: u16) -> String {
+    format!(
+        "proto: redis\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\nmax_retry_after: 0\n\
+        target: {{host: 127.0.0.1, port: {}}}",
+        dir.file("passwords.txt", "123456\nletmein\nqwerty\ns3cret\n"), dir.path("promoted.txt"), dir.path("manifest.json"), port,
+    )
+}
+
+#[test]
+fn test_auth_reconnects() {
+    let replayer = Replayer::fixture("redis-auth.transcript");
+    let dir = TempDir::new("redis-auth");
+    app(&config(&dir, replayer.port)).run().unwrap();
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    replayer.assert_followed(2);
+}
+
+#[test]
+fn test_unauthenticated_target_stops_the_run() {
+    let replayer = Replayer::fixture("redis-nopass.transcript");
+    let dir = TempDir::new("redis-nopass");
+    let err = app(&config(&dir, replayer.port)).run().unwrap_err();
+    assert!(err.to_string().contains("attempt #0: protocol error: the target is unauthenticated"), "{}", err);
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap_or_default(), "");
+}
@@ -0,0 +1,143 @@
+mod testserver;
+
+use std::fs;
+
+use testserver::{app, app_with_env, TempDir};
+
+#[test]
+fn test_generated_passwords() {
+    let dir = TempDir::new("simulated");
+    let usernames = dir.file("usernames.txt", "root\nadmin\n");
+    app(&format!(
+        "proto: simulated\nusernames_file: '{}'\ndict_type: generator\n\
+        dict_props: {{password_length: 2, allowed_chars: ['abc']}}\n\
+        promoted_file: '{}'\nmanifest: '{}'\nrecent_attempts: 0\nstrategy: []\n\
+        target: {{success: ['admin:cb', 'root:ba']}}",
+        usernames, dir.path("promoted.txt"), dir.path("manifest.json"),
+    )).run().unwrap();
+
+    // The run ends at the first hit, root's.
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "ba\n");
+    assert!(fs::read_to_string(dir.path("manifest.json")).unwrap().contains("runs"));
+}
+
+#[test]
+fn test_environment_overrides() {
+    let dir = TempDir::new("simulated-env");
+    let usernames = dir.file("admins.txt", "admin\n");
+    app_with_env(
+        &format!(
+            "proto: simulated\nusernames_file: missing.txt\ndict_type: generator\n\
+            dict_props: {{password_length: 2, allowed_chars: ['abc']}}\n\
+            promoted_file: '{}'\nmanifest: '{}'\nrecent_attempts: 0\nstrategy: []\n\
+            target: {{success: ['root:ba']}}",
+            dir.path("promoted.txt"), dir.path("manifest.json"),
+        ),
+        &[("IMBRUT_USERNAMES_FILE", &usernames), ("IMBRUT__TARGET__SUCCESS", "['admin:cb']")],
+    ).run().unwrap();
+
+    assert_eq!(fs::read_to_string(dir.path("promoted.txt")).unwrap(), "cb\n");
+}
+
+fn several_targets(dir: &TempDir, targets: &str) -> String {
+    let usernames = dir.file("usernames.txt", "root\nadmin\n");
+    format!(
+        "proto: simulated\nusernames_file: '{}'\ndict_type: generator\n\
+        dict_props: {{password_length: 2, allowed_chars: ['abc']}}\n\
+        manifest: '{}'\nrecent_attempts: 0\ncheckpoint_every: 0\nstrategy: []\n\
+        output: {{path: '{}'}}\n{}",
+        usernames, dir.path("manifest.json"), dir.path("hits.json"), targets,
+    )
+}
+
+#[test]
+fn test_several_targets() {
+    let targets = "target:\n\
+        - {name: staging, success: ['admin:cb']}\n\
+        - {name: prod, success: ['root:ba']}\n";
+    for mode in ["sequential", "interleaved"] {
+        let dir = TempDir::new(&format!("targets-{}", mode));
+        let config = format!("{}targets: {{mode: {}}}\n", several_targets(&dir, targets), mode);
+        app(&config).run().unwrap();
+
+        // Every hit is attributed to its target, and so is the summary of each.
+        let records: Vec<serde_json::Value> = fs::read_to_string(dir.path("hits.json")).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let (summaries, hits): (Vec<_>, Vec<_>) = records.iter().partition(|x| x.get("summary").is_some());
+        assert_eq!(summaries.len(), 2, "{}", mode);
+        let mut hits: Vec<_> = hits.into_iter()
+            .map(|hit| {
+                format!("{} {}:{}", hit["target"].as_str().unwrap(), hit["username"].as_str().unwrap(), hit["password"].as_str().unwrap())
+            })
+            .collect();
+        hits.sort();
+        assert_eq!(hits, ["prod root:ba", "staging admin:cb"], "{}", mode);
+    }
+}
+
+#[test]
+fn test_broken_target() {
+    let targets = "target:\n\
+        - {name: broken, proto: nope}\n\
+        - {name: prod, success: ['root:ba']}\n";
+
+    // The other targets still run.
+    let dir = TempDir::new("targets-broken");
+    let err = app(&several_targets(&dir, targets)).run().err().unwrap();
+    assert_eq!(err.to_string(), "strategy error: 1 of 2 targets failed: broken");
+    assert!(fs::read_to_string(dir.path("hits.json")).unwrap().contains("\"prod\""));
+
+    // Unless they are strict.
+    let dir = TempDir::new("targets-strict");
+    let config = format!("{}targets: {{strict: true}}\n", several_targets(&dir, targets));
+    let err = app(&config).run().err().unwrap();
+    assert!(err.to_string().contains("nope"), "{}", err);
+    assert!(!std::path::Path::new(&dir.path("hits.json")).exists());
+}
+
+#[test]
+fn test_limit_then_resume() {
+    let dir = TempDir::new("limits");
+    let config = format!(
+        "{}checkpoint_file: '{}'\ntarget: {{success: ['admin:cc']}}\n",
+        several_targets(&dir, "").replace("checkpoint_every: 0", "checkpoint_every: 1"),
+        dir.path("checkpoint.yml"),
+    );
+
+    // The first run stops on its limit and leaves a checkpoint behind.
+    let limited = app(&format!("{}limits: {{max_attempts: 5}}\n", config));
+    limited.run().unwrap();
+    assert!(limited.limit_reached());
+    assert!(!fs::read_to_string(dir.path("hits.json")).unwrap().contains("admin"));
+    assert!(std::path::Path::new(&dir.path("checkpoint.yml")).exists());
+
+    // The next one picks up from there and finds the hit.
+    let resumed = app(&config);
+    resumed.run().unwrap();
+    assert!(!resumed.limit_reached());
+    assert!(fs::read_to_string(dir.path("hits.json")).unwrap().contains("\"admin\""));
+}
+
+#[test]
+fn test_truncated_wordlist() {
+    let dir = TempDir::new("truncated");
+    let usernames = dir.file("usernames.txt", "admin\n");
+    let gzipped = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wordlists/words.txt.gz")).unwrap();
+    let passwords = dir.path("words.txt.gz");
+    fs::write(&passwords, &gzipped[..gzipped.len() / 2]).unwrap();
+    let config = format!(
+        "proto: simulated\nusernames_file: '{}'\npasswords_file: '{}'\n\
+        manifest: '{}'\npromoted_file: '{}'\nrecent_attempts: 0\ncheckpoint_every: 0\nstrategy: []\n\
+        stop_on: never\noutput: {{path: '{}'}}\ntarget: {{success: ['admin:password1']}}\n",
+        usernames, passwords, dir.path("manifest.json"), dir.path("promoted.txt"), dir.path("hits.json"),
+    );
+
+    // The run fails rather than finishing on the lines it could read, the
+    // hits among them kept.
+    let err = app(&config).run().err().unwrap();
+    assert_eq!(err.kind(), "source.read");
+    assert!(err.to_string().contains("words.txt.gz"), "{}", err);
+    assert!(fs::read_to_string(dir.path("hits.json")).unwrap().contains("\"admin\""));
+}
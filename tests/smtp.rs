@@ -0,0 +1,67 @@
+mod testserver;
+
+use testserver::{app, tls_fixture, Replayer, TempDir};
+
+fn enum_app(port: u16, usernames: &str) -> imbrut::application::Application {
+    app(&format!(
+        "proto: smtp\ntarget: {{host: 127.0.0.1, port: {}}}\nusernames_file: '{}'\n\
+        strategy: []",
+        port, usernames,
+    ))
+}
+
+#[test]
+fn test_vrfy_enumeration() {
+    let replayer = Replayer::fixture("smtp-vrfy.transcript");
+    let dir = TempDir::new("smtp-vrfy");
+    let candidates = dir.file("candidates.txt", "root\nbob\nalice\n");
+    let valid = dir.path("valid.txt");
+
+    let report = enum_app(replayer.port, &candidates).enumerate_usernames(Some(&valid)).unwrap();
+    assert_eq!(report.method, Some(imbrut::proto::smtp::Method::Vrfy));
+    assert_eq!(report.valid, vec!["root", "alice"]);
+    assert_eq!(std::fs::read_to_string(&valid).unwrap(), "root\nalice\n");
+    replayer.assert_followed(1);
+}
+
+#[test]
+fn test_rcpt_fallback() {
+    let replayer = Replayer::fixture("smtp-no-vrfy.transcript");
+    let dir = TempDir::new("smtp-rcpt");
+    let candidates = dir.file("candidates.txt", "root\nbob\n");
+
+    let report = enum_app(replayer.port, &candidates).enumerate_usernames(None).unwrap();
+    assert_eq!(report.method, Some(imbrut::proto::smtp::Method::Rcpt));
+    assert_eq!((report.valid, report.invalid), (vec!["root".to_string()], 1));
+    replayer.assert_followed(1);
+}
+
+/// `admin` with every password of `passwords` against the replayer.
+fn auth_app(dir: &TempDir, port: u16, passwords: &str, extra: &str) -> imbrut::application::Application {
+    app(&format!(
+        "proto: smtp\nusername: admin\npasswords_file: '{}'\npromoted_file: '{}'\nmanifest: '{}'\n\
+        strategy: []\nrecent_attempts: 0\nmax_retry_after: 0\n\
+        target: {{host: 127.0.0.1, port: {}, tls: {{ca_file: '{}'}}{}}}",
+        dir.file("passwords.txt", passwords), dir.path("promoted.txt"), dir.path("manifest.json"),
+        port, tls_fixture("ca.pem"), extra,
+    ))
+}
+
+#[test]
+fn test_auth_plain_starttls() {
+    let replayer = Replayer::fixture("smtp-auth.transcript");
+    let dir = TempDir::new("smtp-auth");
+    auth_app(&dir, replayer.port, "123456\nletmein\ns3cret\n", "").run().unwrap();
+    assert_eq!(std::fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    // The temporary failure was tried again.
+    replayer.assert_followed(4);
+}
+
+#[test]
+fn test_auth_login_implicit_tls() {
+    let replayer = Replayer::fixture("smtp-login.transcript");
+    let dir = TempDir::new("smtp-login");
+    auth_app(&dir, replayer.port, "s3cret\n", ", force_tls: true").run().unwrap();
+    assert_eq!(std::fs::read_to_string(dir.path("promoted.txt")).unwrap(), "s3cret\n");
+    replayer.assert_followed(1);
+}
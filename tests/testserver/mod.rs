@@ -0,0 +1,708 @@
+//! In-process servers the integration tests run the protos against: an
+//! HTTP/1.1 server answering through a closure, plain or over TLS with the
+//! certificates of `tests/fixtures/tls`, and a replayer of recorded
+//! line-based sessions (SMTP, IMAP, POP3, Redis, and the FTP or Telnet
+//! protos to come).
+//!
+//! A transcript fixture holds one or more sessions separated by `---`. In a
+//! session, `S: ` lines are sent by the server and `C: ` lines are expected
+//! from the client, where `*` matches any text. A `TLS` line has the server
+//! start TLS there with the `server` certificate (after STARTTLS, or first
+//! thing for implicit TLS). Blank lines and `#` comments are skipped. Every
+//! connection replays the next session; the last one is replayed for any
+//! further connections.
+//!
+//! Binary protocols get servers of their own: `LdapServer` answers simple
+//! binds, `MysqlServer` and `PostgresServer` password logins.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use imbrut::application::Application;
+use imbrut::settings::{self, Settings};
+use openssl::hash::{hash, MessageDigest};
+use openssl::sha::sha1;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
+
+/// How long a replayer waits for the client before giving up on a session.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Config files written by `app`, numbered apart within a test binary.
+static CONFIGS: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory under the system temp dir, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("imbrut-it-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Write `contents` to `name` in the directory and return its path.
+    pub fn file(&self, name: &str, contents: &str) -> String {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    pub fn path(&self, name: &str) -> String {
+        self.0.join(name).display().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// An application configured from `yaml`, as if read from `test.yml`.
+pub fn app(yaml: &str) -> Application {
+    app_with_env(yaml, &[])
+}
+
+/// An application configured the way a run reads its config: `yaml` as a
+/// config file over the defaults, migrated, under the overrides of `env`.
+pub fn app_with_env(yaml: &str, env: &[(&str, &str)]) -> Application {
+    let dir = TempDir::new(&format!("config-{}", CONFIGS.fetch_add(1, Ordering::Relaxed)));
+    let file = dir.file("test.yml", yaml);
+    let env: Vec<_> = env.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+    let layered = settings::layered_config(&file, &env).unwrap();
+    Application::with_settings(Settings::from_layered(file, &layered).unwrap())
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Names are lowercased.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, body: &str) -> Self {
+        Self { status, headers: Vec::new(), body: body.to_string() }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// Path of a file in `tests/fixtures/tls`.
+pub fn tls_fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name).display().to_string()
+}
+
+/// HTTP/1.1 server on a random local port, one request per connection.
+pub struct HttpServer {
+    scheme: &'static str,
+    addr: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl HttpServer {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let captured = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let Some(request) = read_request(&stream) else { continue };
+                let response = handler(&request);
+                captured.lock().unwrap().push(request);
+                write_response(stream, &response);
+            }
+        });
+        Self { scheme: "http", addr, requests }
+    }
+
+    /// Over TLS with the certificate `<cert>.pem`, issued by `ca.pem`:
+    /// `server` for 127.0.0.1, `expired` for it but expired since 2021,
+    /// `wronghost` for wrong.example. With `client_auth`, connections without
+    /// a client certificate issued by `ca.pem` are refused during the handshake.
+    pub fn start_tls(cert: &str, client_auth: bool, handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate_chain_file(tls_fixture(&format!("{}.pem", cert))).unwrap();
+        acceptor.set_private_key_file(tls_fixture(&format!("{}.key", cert)), SslFiletype::PEM).unwrap();
+        if client_auth {
+            acceptor.set_ca_file(tls_fixture("ca.pem")).unwrap();
+            acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let captured = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let Ok(mut stream) = acceptor.accept(stream) else { continue };
+                let Some(request) = read_request(&mut stream) else { continue };
+                let response = handler(&request);
+                captured.lock().unwrap().push(request);
+                write_response(&mut stream, &response);
+                let _ = stream.shutdown();
+            }
+        });
+        Self { scheme: "https", addr, requests }
+    }
+
+    pub fn uri(&self, path: &str) -> String {
+        format!("{}://{}{}", self.scheme, self.addr, path)
+    }
+
+    /// Requests answered so far, oldest first.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: impl Read) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.trim_end().split_once(':') {
+            headers.insert(name.to_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers.get("content-length").and_then(|x| x.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some(Request { method, path, headers, body: String::from_utf8_lossy(&body).into() })
+}
+
+fn write_response(mut stream: impl Write, response: &Response) {
+    let headers: String = response.headers.iter().map(|(k, v)| format!("{}: {}\r\n", k, v)).collect();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} X\r\n{}content-length: {}\r\nconnection: close\r\n\r\n{}",
+        response.status, headers, response.body.len(), response.body
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Server(String),
+    Client(String),
+    Tls,
+}
+
+pub type Session = Vec<Line>;
+
+/// Parse a transcript fixture into its sessions.
+pub fn parse_transcript(text: &str) -> Result<Vec<Session>, String> {
+    let mut sessions = vec![Vec::new()];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "---" {
+            sessions.push(Vec::new());
+            continue;
+        }
+        let session = sessions.last_mut().unwrap();
+        if line == "TLS" {
+            session.push(Line::Tls);
+            continue;
+        }
+        match line.split_once(": ").or_else(|| line.split_once(':')) {
+            Some(("S", text)) => session.push(Line::Server(text.to_string())),
+            Some(("C", text)) => session.push(Line::Client(text.to_string())),
+            _ => return Err(format!("line {}: expected `S: `, `C: ` or `TLS`, got `{}`", number + 1, line)),
+        }
+    }
+    if sessions.iter().any(Vec::is_empty) {
+        return Err("empty session".to_string());
+    }
+    Ok(sessions)
+}
+
+/// Whether `line` matches `pattern`, where `*` stands for any text.
+pub fn matches(pattern: &str, line: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = line.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else { return rest.is_empty() };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Serves transcript sessions on a random local port.
+pub struct Replayer {
+    pub port: u16,
+    /// Deviations from the transcript, one per failed session.
+    errors: Arc<Mutex<Vec<String>>>,
+    /// Sessions replayed to the end.
+    completed: Arc<Mutex<usize>>,
+}
+
+impl Replayer {
+    pub fn start(sessions: Vec<Session>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (errors, completed) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(0)));
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate_chain_file(tls_fixture("server.pem")).unwrap();
+        acceptor.set_private_key_file(tls_fixture("server.key"), SslFiletype::PEM).unwrap();
+        let acceptor = acceptor.build();
+
+        let (errors_, completed_) = (errors.clone(), completed.clone());
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { continue };
+                let session = &sessions[i.min(sessions.len() - 1)];
+                match replay(stream, session, &acceptor) {
+                    Ok(()) => *completed_.lock().unwrap() += 1,
+                    Err(e) => errors_.lock().unwrap().push(format!("connection {}: {}", i + 1, e)),
+                }
+            }
+        });
+        Self { port, errors, completed }
+    }
+
+    /// Replay the sessions of the fixture `tests/fixtures/<name>`.
+    pub fn fixture(name: &str) -> Self {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        let text = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        Self::start(parse_transcript(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)))
+    }
+
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    pub fn completed(&self) -> usize {
+        *self.completed.lock().unwrap()
+    }
+
+    /// Wait briefly for `sessions` connections to wind down.
+    pub fn wait(&self, sessions: usize) {
+        for _ in 0..100 {
+            if self.completed() + self.errors().len() >= sessions {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Fail unless `sessions` connections followed the transcript.
+    pub fn assert_followed(&self, sessions: usize) {
+        self.wait(sessions);
+        assert_eq!(self.errors(), Vec::<String>::new());
+        assert_eq!(self.completed(), sessions);
+    }
+}
+
+/// A replayed connection, before or after TLS started.
+enum Channel {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Read for Channel {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Channel {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+fn replay(stream: TcpStream, session: &Session, acceptor: &SslAcceptor) -> Result<(), String> {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(Channel::Plain(stream));
+    for line in session {
+        match line {
+            Line::Server(text) => reader.get_mut().write_all(format!("{}\r\n", text).as_bytes()).map_err(|e| e.to_string())?,
+            Line::Tls => {
+                let Channel::Plain(stream) = reader.into_inner() else { return Err("TLS started twice".to_string()) };
+                let stream = acceptor.accept(stream).map_err(|e| format!("TLS handshake failed: {}", e))?;
+                reader = BufReader::new(Channel::Tls(Box::new(stream)));
+            }
+            Line::Client(pattern) => {
+                let mut received = String::new();
+                match reader.read_line(&mut received) {
+                    Ok(0) => return Err(format!("closed, expected `{}`", pattern)),
+                    Ok(_) if matches(pattern, received.trim_end_matches(['\r', '\n'])) => {}
+                    Ok(_) => return Err(format!("expected `{}`, got `{}`", pattern, received.trim_end())),
+                    Err(e) => return Err(format!("{}, expected `{}`", e, pattern)),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A BER element of the LDAP test server, short lengths only.
+fn ber(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    match contents.len() {
+        len @ 0..=0x7f => element.push(len as u8),
+        len => element.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    element.extend_from_slice(contents);
+    element
+}
+
+/// The tag and contents of the first element of `data`, and the rest.
+fn ber_split(data: &[u8]) -> (u8, &[u8], &[u8]) {
+    let (len, start) = match data[1] {
+        len @ 0..=0x7f => (len as usize, 2),
+        0x81 => (data[2] as usize, 3),
+        _ => (u16::from_be_bytes([data[2], data[3]]) as usize, 4),
+    };
+    (data[0], &data[start..start + len], &data[start + len..])
+}
+
+/// A simple bind received by `LdapServer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bind {
+    pub dn: String,
+    pub password: String,
+    pub tls: bool,
+}
+
+/// LDAP server on a random local port answering simple binds with the
+/// result code and message `handler` gives for the DN and password. With
+/// `ldaps` TLS starts first thing with the `server` certificate; otherwise
+/// StartTLS is honoured.
+pub struct LdapServer {
+    pub port: u16,
+    binds: Arc<Mutex<Vec<Bind>>>,
+}
+
+impl LdapServer {
+    pub fn start(ldaps: bool, handler: impl Fn(&str, &str) -> (u8, String) + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let binds = Arc::new(Mutex::new(Vec::new()));
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate_chain_file(tls_fixture("server.pem")).unwrap();
+        acceptor.set_private_key_file(tls_fixture("server.key"), SslFiletype::PEM).unwrap();
+        let acceptor = acceptor.build();
+
+        let captured = binds.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                stream.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+                let mut channel = Channel::Plain(stream);
+                if ldaps {
+                    let Channel::Plain(stream) = channel else { unreachable!() };
+                    let Ok(stream) = acceptor.accept(stream) else { continue };
+                    channel = Channel::Tls(Box::new(stream));
+                }
+                while let Some((id, op, contents)) = ldap_message(&mut channel) {
+                    let tls = matches!(channel, Channel::Tls(_));
+                    let (reply, code, message) = match op {
+                        // Bind: version, name, simple password.
+                        0x60 => {
+                            let (_, _, rest) = ber_split(&contents);
+                            let (_, dn, rest) = ber_split(rest);
+                            let (_, password, _) = ber_split(rest);
+                            let (dn, password) = (String::from_utf8_lossy(dn).into_owned(), String::from_utf8_lossy(password).into_owned());
+                            let (code, message) = handler(&dn, &password);
+                            captured.lock().unwrap().push(Bind { dn, password, tls });
+                            (0x61, code, message)
+                        }
+                        0x77 if !tls => (0x78, 0, String::new()),
+                        // Unbind, or anything unexpected.
+                        _ => break,
+                    };
+                    let result = [&[0x0a, 0x01, code, 0x04, 0x00][..], &ber(0x04, message.as_bytes())].concat();
+                    let response = ber(0x30, &[&[0x02, 0x01, id][..], &ber(reply, &result)].concat());
+                    if channel.write_all(&response).is_err() {
+                        break;
+                    }
+                    if reply == 0x78 {
+                        let Channel::Plain(stream) = channel else { unreachable!() };
+                        let Ok(stream) = acceptor.accept(stream) else { break };
+                        channel = Channel::Tls(Box::new(stream));
+                    }
+                }
+            }
+        });
+        Self { port, binds }
+    }
+
+    pub fn binds(&self) -> Vec<Bind> {
+        self.binds.lock().unwrap().clone()
+    }
+}
+
+/// The message id, operation tag and operation contents of the next
+/// LDAPMessage, `None` once the client is gone.
+fn ldap_message(channel: &mut Channel) -> Option<(u8, u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    channel.read_exact(&mut header).ok()?;
+    let mut len = header[1] as usize;
+    if header[1] & 0x80 != 0 {
+        let mut bytes = vec![0; (header[1] & 0x7f) as usize];
+        channel.read_exact(&mut bytes).ok()?;
+        len = bytes.iter().fold(0, |acc, x| acc << 8 | *x as usize);
+    }
+    let mut message = vec![0; len];
+    channel.read_exact(&mut message).ok()?;
+    let (_, id, rest) = ber_split(&message);
+    let (op, contents, _) = ber_split(rest);
+    Some((*id.last()?, op, contents.to_vec()))
+}
+
+/// A login received by `MysqlServer` or `PostgresServer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Login {
+    pub user: String,
+    pub database: String,
+    pub accepted: bool,
+}
+
+/// Logins of a database server, accepted for the `(user, password)` pairs
+/// it was started with.
+#[derive(Clone)]
+struct Accounts {
+    passwords: Arc<HashMap<String, String>>,
+    logins: Arc<Mutex<Vec<Login>>>,
+}
+
+impl Accounts {
+    fn new(accounts: &[(&str, &str)]) -> Self {
+        Self {
+            passwords: Arc::new(accounts.iter().map(|(user, password)| (user.to_string(), password.to_string())).collect()),
+            logins: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Record a login as accepted when `check` holds for the password of
+    /// `user`.
+    fn login(&self, user: &str, database: &str, check: impl Fn(&str) -> bool) -> bool {
+        let accepted = self.passwords.get(user).is_some_and(|x| check(x));
+        self.logins.lock().unwrap().push(Login { user: user.to_string(), database: database.to_string(), accepted });
+        accepted
+    }
+
+    /// Serve every connection to a random local port with `session`.
+    fn serve(&self, session: fn(TcpStream, &Accounts) -> Option<()>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accounts = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                stream.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+                session(stream, &accounts);
+            }
+        });
+        port
+    }
+}
+
+/// Bytes up to a NUL, and the rest after it.
+fn nul_terminated(data: &[u8]) -> (String, &[u8]) {
+    let end = data.iter().position(|x| *x == 0).unwrap_or(data.len());
+    (String::from_utf8_lossy(&data[..end]).into_owned(), data.get(end + 1..).unwrap_or_default())
+}
+
+const MYSQL_SCRAMBLE: &[u8; 20] = b"0123456789abcdefghij";
+const CLIENT_CONNECT_WITH_DB: u32 = 0x8;
+
+/// MySQL server on a random local port checking mysql_native_password
+/// logins against `accounts`.
+pub struct MysqlServer {
+    pub port: u16,
+    accounts: Accounts,
+}
+
+impl MysqlServer {
+    pub fn start(accounts: &[(&str, &str)]) -> Self {
+        let accounts = Accounts::new(accounts);
+        Self { port: accounts.serve(mysql_session), accounts }
+    }
+
+    pub fn logins(&self) -> Vec<Login> {
+        self.accounts.logins.lock().unwrap().clone()
+    }
+}
+
+fn mysql_read(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).ok()?;
+    let mut payload = vec![0; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+    stream.read_exact(&mut payload).ok()?;
+    Some(payload)
+}
+
+fn mysql_write(stream: &mut TcpStream, seq: u8, payload: &[u8]) -> Option<()> {
+    let len = (payload.len() as u32).to_le_bytes();
+    stream.write_all(&[&[len[0], len[1], len[2], seq], payload].concat()).ok()
+}
+
+fn mysql_session(mut stream: TcpStream, accounts: &Accounts) -> Option<()> {
+    // Protocol 10, the scramble in two parts, CLIENT_PLUGIN_AUTH and
+    // CLIENT_SECURE_CONNECTION.
+    let mut greeting = vec![10];
+    greeting.extend_from_slice(b"8.0.36\0");
+    greeting.extend_from_slice(&7u32.to_le_bytes());
+    greeting.extend_from_slice(&MYSQL_SCRAMBLE[..8]);
+    greeting.push(0);
+    greeting.extend_from_slice(&0xf7ffu16.to_le_bytes());
+    greeting.extend_from_slice(&[45, 2, 0]);
+    greeting.extend_from_slice(&0x81ffu16.to_le_bytes());
+    greeting.push(21);
+    greeting.extend_from_slice(&[0; 10]);
+    greeting.extend_from_slice(&MYSQL_SCRAMBLE[8..]);
+    greeting.push(0);
+    greeting.extend_from_slice(b"mysql_native_password\0");
+    mysql_write(&mut stream, 0, &greeting)?;
+
+    // Flags, max packet size, charset and filler, then the user, the
+    // token and the database.
+    let response = mysql_read(&mut stream)?;
+    let flags = u32::from_le_bytes(response.get(..4)?.try_into().ok()?);
+    let (user, rest) = nul_terminated(response.get(32..)?);
+    let len = *rest.first()? as usize;
+    let token = rest.get(1..1 + len)?;
+    let database = match flags & CLIENT_CONNECT_WITH_DB {
+        0 => String::new(),
+        _ => nul_terminated(&rest[1 + len..]).0,
+    };
+    let accepted = accounts.login(&user, &database, |password| {
+        let stored = sha1(&sha1(password.as_bytes()));
+        let mask = sha1(&[&MYSQL_SCRAMBLE[..], &stored].concat());
+        let candidate: Vec<u8> = token.iter().zip(mask).map(|(x, y)| x ^ y).collect();
+        token.len() == 20 && sha1(&candidate) == stored
+    });
+    match accepted {
+        true => mysql_write(&mut stream, 2, &[0, 0, 0, 2, 0, 0, 0]),
+        // ER_ACCESS_DENIED_ERROR
+        false => mysql_write(&mut stream, 2, &[&[0xff][..], &1045u16.to_le_bytes(), b"#28000Access denied"].concat()),
+    }
+}
+
+/// PostgreSQL server on a random local port asking for md5 passwords and
+/// checking them against `accounts`.
+pub struct PostgresServer {
+    pub port: u16,
+    accounts: Accounts,
+}
+
+impl PostgresServer {
+    pub fn start(accounts: &[(&str, &str)]) -> Self {
+        let accounts = Accounts::new(accounts);
+        Self { port: accounts.serve(postgres_session), accounts }
+    }
+
+    pub fn logins(&self) -> Vec<Login> {
+        self.accounts.logins.lock().unwrap().clone()
+    }
+}
+
+/// The body of the next message; the startup message has no type byte.
+fn postgres_read(stream: &mut TcpStream, startup: bool) -> Option<Vec<u8>> {
+    if !startup {
+        stream.read_exact(&mut [0]).ok()?;
+    }
+    let mut len = [0; 4];
+    stream.read_exact(&mut len).ok()?;
+    let mut body = vec![0; (u32::from_be_bytes(len) as usize).checked_sub(4)?];
+    stream.read_exact(&mut body).ok()?;
+    Some(body)
+}
+
+fn postgres_write(stream: &mut TcpStream, kind: u8, body: &[u8]) -> Option<()> {
+    stream.write_all(&[&[kind][..], &(body.len() as u32 + 4).to_be_bytes(), body].concat()).ok()
+}
+
+fn md5_hex(data: &[u8]) -> String {
+    hash(MessageDigest::md5(), data).unwrap().iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn postgres_session(mut stream: TcpStream, accounts: &Accounts) -> Option<()> {
+    // Protocol version, then `key\0value\0` pairs.
+    let startup = postgres_read(&mut stream, true)?;
+    let params: Vec<String> = startup.get(4..)?.split(|x| *x == 0).map(|x| String::from_utf8_lossy(x).into_owned()).collect();
+    let param = |name: &str| params.chunks(2).find(|x| x[0] == name).and_then(|x| x.get(1)).cloned().unwrap_or_default();
+    let (user, database) = (param("user"), param("database"));
+
+    let salt = b"salt";
+    postgres_write(&mut stream, b'R', &[&5u32.to_be_bytes()[..], salt].concat())?;
+    let (sent, _) = nul_terminated(&postgres_read(&mut stream, false)?);
+    let accepted = accounts.login(&user, &database, |password| {
+        let inner = md5_hex(format!("{}{}", password, user).as_bytes());
+        sent == format!("md5{}", md5_hex(&[inner.as_bytes(), salt].concat()))
+    });
+    match accepted {
+        true => postgres_write(&mut stream, b'R', &0u32.to_be_bytes()),
+        false => postgres_write(&mut stream, b'E', format!("SFATAL\0C28P01\0Mpassword authentication failed for user \"{}\"\0\0", user).as_bytes()),
+    }
+}
@@ -0,0 +1,57 @@
+mod testserver;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+use testserver::{matches, parse_transcript, Line, Replayer};
+
+/// Send `line` on a new connection and return the first server line.
+fn greeted(replayer: &Replayer, line: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", replayer.port)).unwrap();
+    let mut greeting = String::new();
+    BufReader::new(stream.try_clone().unwrap()).read_line(&mut greeting).unwrap();
+    stream.write_all(line.as_bytes()).unwrap();
+    greeting
+}
+
+#[test]
+fn test_parse() {
+    let sessions = parse_transcript("# greeting\nS: 220 ready\nC: USER *\n\n---\nS: 421 busy\n").unwrap();
+    assert_eq!(sessions, vec![
+        vec![Line::Server("220 ready".to_string()), Line::Client("USER *".to_string())],
+        vec![Line::Server("421 busy".to_string())],
+    ]);
+    assert_eq!(parse_transcript("S: 220\nX: nope").unwrap_err(), "line 2: expected `S: `, `C: ` or `TLS`, got `X: nope`");
+    assert_eq!(parse_transcript("TLS\nS: 220 ready").unwrap(), vec![vec![Line::Tls, Line::Server("220 ready".to_string())]]);
+    assert_eq!(parse_transcript("S: 220\n---\n").unwrap_err(), "empty session");
+}
+
+#[test]
+fn test_patterns() {
+    assert!(matches("EHLO localhost", "EHLO localhost"));
+    assert!(!matches("EHLO localhost", "EHLO localhost2"));
+    assert!(matches("VRFY *", "VRFY root"));
+    assert!(matches("RCPT TO:<*@*>", "RCPT TO:<root@example.test>"));
+    assert!(!matches("RCPT TO:<*@*>", "RCPT TO:<root>"));
+    assert!(!matches("a*a", "a"));
+    assert!(matches("*", ""));
+}
+
+#[test]
+fn test_sessions_per_connection() {
+    let replayer = Replayer::start(parse_transcript("S: 220 ready\nC: HELO *\nS: 250 ok\n---\nS: 421 busy").unwrap());
+    assert_eq!(greeted(&replayer, "HELO me\r\n"), "220 ready\r\n");
+    assert_eq!(greeted(&replayer, "EHLO me\r\n"), "421 busy\r\n");
+    // The last session repeats.
+    assert_eq!(greeted(&replayer, "EHLO me\r\n"), "421 busy\r\n");
+    replayer.assert_followed(3);
+}
+
+#[test]
+fn test_deviations_are_reported() {
+    let replayer = Replayer::start(parse_transcript("S: 220 ready\nC: HELO *").unwrap());
+    greeted(&replayer, "EHLO me\r\n");
+    replayer.wait(1);
+    assert_eq!(replayer.errors(), vec!["connection 1: expected `HELO *`, got `EHLO me`"]);
+    assert_eq!(replayer.completed(), 0);
+}